@@ -5,10 +5,49 @@ use chrono::Utc;
 use chrono::{DateTime, TimeZone};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use thiserror::Error;
 use zksync_basic_types::U256;
 
 use super::ExecutedOperations;
 
+/// Fixed gas cost (in gas units) charged per operation on the Commit transaction, regardless of
+/// its size, approximating the fixed overhead of decoding and storing an operation on-chain.
+const COMMIT_BASE_GAS_PER_OP: u64 = 1_000;
+/// Additional Commit gas charged per chunk occupied by an operation, approximating the calldata
+/// cost that scales with operation size.
+const COMMIT_GAS_PER_CHUNK: u64 = 9_000;
+/// Fixed gas cost (in gas units) charged per operation on the Verify transaction, approximating
+/// the fixed overhead of the proof verification circuit per operation.
+const VERIFY_BASE_GAS_PER_OP: u64 = 500;
+/// Additional Verify gas charged per chunk occupied by an operation, approximating the
+/// circuit cost that scales with operation size.
+const VERIFY_GAS_PER_CHUNK: u64 = 15_000;
+
+/// How the Commit/Verify gas limits should be determined when constructing a block via
+/// [`IncompleteBlock::pack_into_blocks`].
+#[derive(Debug, Clone, Copy)]
+pub enum GasLimits {
+    /// Use the given Commit/Verify gas limits as-is.
+    Fixed(U256, U256),
+    /// Derive Commit/Verify gas limits from the block's operations (see
+    /// [`IncompleteBlock::estimate_gas_limits`]), inflated by this safety margin percentage.
+    Estimated { safety_margin_percent: u64 },
+}
+
+/// Error returned when the provided operations cannot be packed into the supported block sizes.
+#[derive(Debug, Error)]
+pub enum BlockPackingError {
+    /// A single operation's chunk count exceeds the largest supported block size, so it can
+    /// never fit into any block regardless of how the transactions are split up.
+    #[error(
+        "operation requires {op_chunks} chunks, which exceeds the maximum supported block size of {max_block_size}"
+    )]
+    OperationTooLarge {
+        op_chunks: usize,
+        max_block_size: usize,
+    },
+}
+
 /// Sealed, but not yet completed zkSync block data.
 /// This structure contains data available in the state keeper when the block is sealed,
 /// but misses data to calculate the commitment (mainly, the root hash of the block).
@@ -35,6 +74,21 @@ pub struct IncompleteBlock {
     pub timestamp: u64,
 }
 
+/// A checkpoint of an [`IncompleteBlock`]'s operations, captured by [`IncompleteBlock::snapshot`]
+/// and restored by [`IncompleteBlock::revert_to`].
+///
+/// Borrows the `evm_snapshot`/revert model: it lets the state keeper speculatively append
+/// executed operations to a block and cheaply unwind them (e.g. on reorg or failed execution)
+/// without rebuilding the block from scratch.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockSnapshot {
+    block_number: BlockNumber,
+    processed_priority_ops: (u64, u64),
+    block_transactions_len: usize,
+    commit_gas_limit: U256,
+    verify_gas_limit: U256,
+}
+
 impl IncompleteBlock {
     /// Creates a new `IncompleteBlock` object.
     #[allow(clippy::too_many_arguments)]
@@ -91,6 +145,176 @@ impl IncompleteBlock {
         block
     }
 
+    /// Creates a new block exactly like [`Self::new_from_available_block_sizes`], but estimating
+    /// `commit_gas_limit` and `verify_gas_limit` from `block_transactions` instead of requiring
+    /// them as inputs.
+    ///
+    /// See [`Self::estimate_gas_limits`] for the cost model and the meaning of
+    /// `gas_safety_margin_percent`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no supported block size to fit all the transactions.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_from_available_block_sizes_with_estimated_gas(
+        block_number: BlockNumber,
+        fee_account: AccountId,
+        block_transactions: Vec<ExecutedOperations>,
+        processed_priority_ops: (u64, u64),
+        available_block_chunks_sizes: &[usize],
+        gas_safety_margin_percent: u64,
+        timestamp: u64,
+    ) -> Self {
+        let mut block = Self {
+            block_number,
+            fee_account,
+            block_transactions,
+            processed_priority_ops,
+            block_chunks_size: 0,
+            commit_gas_limit: U256::zero(),
+            verify_gas_limit: U256::zero(),
+            timestamp,
+        };
+        block.block_chunks_size = block.smallest_block_size(available_block_chunks_sizes);
+        let (commit_gas_limit, verify_gas_limit) =
+            block.estimate_gas_limits(gas_safety_margin_percent);
+        block.commit_gas_limit = commit_gas_limit;
+        block.verify_gas_limit = verify_gas_limit;
+        block
+    }
+
+    /// Creates a new `IncompleteBlock` object, estimating `commit_gas_limit` and
+    /// `verify_gas_limit` from `block_transactions` instead of requiring them as inputs.
+    ///
+    /// See [`Self::estimate_gas_limits`] for the cost model and the meaning of
+    /// `gas_safety_margin_percent`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_estimated_gas(
+        block_number: BlockNumber,
+        fee_account: AccountId,
+        block_transactions: Vec<ExecutedOperations>,
+        processed_priority_ops: (u64, u64),
+        block_chunks_size: usize,
+        gas_safety_margin_percent: u64,
+        timestamp: u64,
+    ) -> Self {
+        let mut block = Self {
+            block_number,
+            fee_account,
+            block_transactions,
+            processed_priority_ops,
+            block_chunks_size,
+            commit_gas_limit: U256::zero(),
+            verify_gas_limit: U256::zero(),
+            timestamp,
+        };
+        let (commit_gas_limit, verify_gas_limit) =
+            block.estimate_gas_limits(gas_safety_margin_percent);
+        block.commit_gas_limit = commit_gas_limit;
+        block.verify_gas_limit = verify_gas_limit;
+        block
+    }
+
+    /// Estimates the Commit and Verify gas limits for this block, mirroring an
+    /// `eth_estimateGas`-style estimation instead of relying on fixed caller-supplied values.
+    ///
+    /// The cost model sums a fixed base cost plus a per-chunk cost over every executed operation,
+    /// then inflates the total by `safety_margin_percent` to absorb estimation error.
+    pub fn estimate_gas_limits(&self, safety_margin_percent: u64) -> (U256, U256) {
+        let chunk_counts = self
+            .block_transactions
+            .iter()
+            .filter_map(ExecutedOperations::get_executed_op)
+            .map(ZkSyncOp::chunks);
+        estimate_gas_for_chunk_counts(chunk_counts, safety_margin_percent)
+    }
+
+    /// Packs `block_transactions` into one or more blocks, greedily filling each block up to the
+    /// largest supported chunk size and sealing it with the smallest size that fits the
+    /// accumulated operations.
+    ///
+    /// Transactions are kept in their original order; a block is sealed as soon as the next
+    /// operation would no longer fit, and a new block is started for it. The priority-op cursor
+    /// is carried across the resulting blocks, so the second element of one block's
+    /// `processed_priority_ops` is always the first element of the next block's. Returns an
+    /// empty `Vec` if `block_transactions` is empty.
+    ///
+    /// Unlike [`Self::new_from_available_block_sizes`], this never panics on oversized input:
+    /// if the transactions can't fit into a single block, they're split across several instead.
+    ///
+    /// `gas_limits` is applied independently to each produced block: with [`GasLimits::Fixed`]
+    /// every block gets the same caller-supplied limits, while [`GasLimits::Estimated`] derives
+    /// each block's limits from only the operations sealed into that block (see
+    /// [`Self::estimate_gas_limits`]), since splitting can otherwise leave a block's limits far
+    /// from its actual chunk count.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BlockPackingError::OperationTooLarge`] if a single operation's chunk count
+    /// exceeds the largest entry in `available_block_chunks_sizes`, since no amount of splitting
+    /// can make such an operation fit.
+    #[allow(clippy::too_many_arguments)]
+    pub fn pack_into_blocks(
+        starting_block_number: BlockNumber,
+        fee_account: AccountId,
+        block_transactions: Vec<ExecutedOperations>,
+        initial_processed_priority_ops: (u64, u64),
+        available_block_chunks_sizes: &[usize],
+        gas_limits: GasLimits,
+        timestamp: u64,
+    ) -> Result<Vec<Self>, BlockPackingError> {
+        let op_chunks_and_priority: Vec<(usize, bool)> = block_transactions
+            .iter()
+            .map(|tx| {
+                let op_chunks = tx
+                    .get_executed_op()
+                    .map(ZkSyncOp::chunks)
+                    .unwrap_or_default();
+                (op_chunks, matches!(tx, ExecutedOperations::PriorityOp(_)))
+            })
+            .collect();
+        let groups = pack_chunk_groups(&op_chunks_and_priority, available_block_chunks_sizes)?;
+
+        let mut blocks = Vec::with_capacity(groups.len());
+        let mut remaining_txs = block_transactions.into_iter();
+        let mut block_number = starting_block_number;
+        let mut priority_ops_cursor = initial_processed_priority_ops.0;
+
+        for group in groups {
+            let next_priority_ops_cursor = priority_ops_cursor + group.priority_ops;
+            let block_transactions: Vec<ExecutedOperations> =
+                (&mut remaining_txs).take(group.tx_count).collect();
+            let (commit_gas_limit, verify_gas_limit) = match gas_limits {
+                GasLimits::Fixed(commit_gas_limit, verify_gas_limit) => {
+                    (commit_gas_limit, verify_gas_limit)
+                }
+                GasLimits::Estimated {
+                    safety_margin_percent,
+                } => {
+                    let chunk_counts = block_transactions
+                        .iter()
+                        .filter_map(ExecutedOperations::get_executed_op)
+                        .map(ZkSyncOp::chunks);
+                    estimate_gas_for_chunk_counts(chunk_counts, safety_margin_percent)
+                }
+            };
+            blocks.push(Self {
+                block_number,
+                fee_account,
+                block_transactions,
+                processed_priority_ops: (priority_ops_cursor, next_priority_ops_cursor),
+                block_chunks_size: group.block_chunks_size,
+                commit_gas_limit,
+                verify_gas_limit,
+                timestamp,
+            });
+            priority_ops_cursor = next_priority_ops_cursor;
+            block_number += 1;
+        }
+
+        Ok(blocks)
+    }
+
     fn chunks_used(&self) -> usize {
         self.block_transactions
             .iter()
@@ -108,13 +332,224 @@ impl IncompleteBlock {
         Utc.timestamp_opt(self.timestamp as i64, 0).unwrap()
     }
 
+    /// Panic-free, fallible counterpart of [`Self::timestamp_utc`].
+    ///
+    /// Returns `None` if `self.timestamp` doesn't represent a valid `DateTime<Utc>` (e.g. it's
+    /// out of the range representable by `chrono`), instead of panicking.
+    pub fn try_timestamp_utc(&self) -> Option<DateTime<Utc>> {
+        if self.timestamp > i64::MAX as u64 {
+            return None;
+        }
+        Utc.timestamp_opt(self.timestamp as i64, 0).single()
+    }
+
+    /// Checks that `self.timestamp` is plausible relative to `now`, rejecting blocks whose
+    /// timestamp is too far in the future.
+    ///
+    /// `now` must be supplied by the caller rather than read from the local clock here, so that
+    /// the check produces the same result regardless of which node's clock is used to perform it.
+    pub fn validate_timestamp(
+        &self,
+        now: u64,
+        max_future_drift: Duration,
+    ) -> Result<(), TimestampError> {
+        let max_timestamp = now.saturating_add(max_future_drift.as_secs());
+        if self.timestamp > max_timestamp {
+            return Err(TimestampError::TooFarInFuture {
+                timestamp: self.timestamp,
+                max_timestamp,
+            });
+        }
+        Ok(())
+    }
+
     pub fn elapsed(&self) -> Duration {
-        (Utc::now() - self.timestamp_utc())
-            .to_std()
-            .unwrap_or_default()
+        let timestamp = match self.try_timestamp_utc() {
+            Some(timestamp) => timestamp,
+            // An out-of-range timestamp can't have meaningfully elapsed.
+            None => return Duration::default(),
+        };
+        (Utc::now() - timestamp).to_std().unwrap_or_default()
+    }
+
+    /// Captures a checkpoint of this block's operations, to later be restored with
+    /// [`Self::revert_to`].
+    pub fn snapshot(&self) -> BlockSnapshot {
+        BlockSnapshot {
+            block_number: self.block_number,
+            processed_priority_ops: self.processed_priority_ops,
+            block_transactions_len: self.block_transactions.len(),
+            commit_gas_limit: self.commit_gas_limit,
+            verify_gas_limit: self.verify_gas_limit,
+        }
+    }
+
+    /// Restores this block to the state captured by `snapshot`, discarding any operations
+    /// appended since then.
+    ///
+    /// This also restores `commit_gas_limit` and `verify_gas_limit` to their values at snapshot
+    /// time, since those are derived from `block_transactions` (see
+    /// [`Self::estimate_gas_limits`]) and would otherwise go stale relative to the truncated set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_transactions` is now shorter than the length recorded in `snapshot`
+    /// (e.g. operations were already reverted past that point). Whether `snapshot` was taken
+    /// from this block is not checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RevertError::NoFittingBlockSize`] if `available_block_chunks_sizes` doesn't
+    /// contain a size large enough for the truncated `block_transactions` (e.g. it's a different,
+    /// more restrictive list than the one the block was originally packed with).
+    pub fn revert_to(
+        &mut self,
+        snapshot: BlockSnapshot,
+        available_block_chunks_sizes: &[usize],
+    ) -> Result<(), RevertError> {
+        assert!(
+            self.block_transactions.len() >= snapshot.block_transactions_len,
+            "cannot revert to a snapshot taken after the current state"
+        );
+        self.block_number = snapshot.block_number;
+        self.processed_priority_ops = snapshot.processed_priority_ops;
+        self.block_transactions
+            .truncate(snapshot.block_transactions_len);
+
+        let chunks_used = self.chunks_used();
+        self.block_chunks_size = available_block_chunks_sizes
+            .iter()
+            .copied()
+            .find(|&block_size| block_size >= chunks_used)
+            .ok_or(RevertError::NoFittingBlockSize { chunks_used })?;
+
+        self.commit_gas_limit = snapshot.commit_gas_limit;
+        self.verify_gas_limit = snapshot.verify_gas_limit;
+        Ok(())
     }
 }
 
+/// Error returned by [`IncompleteBlock::revert_to`].
+#[derive(Debug, Error)]
+pub enum RevertError {
+    /// None of `available_block_chunks_sizes` is large enough to hold the block's chunks used
+    /// after truncating back to the snapshot.
+    #[error(
+        "reverted block uses {chunks_used} chunks, which doesn't fit any of the available block sizes"
+    )]
+    NoFittingBlockSize { chunks_used: usize },
+}
+
+/// Error returned by [`IncompleteBlock::validate_timestamp`].
+#[derive(Debug, Error)]
+pub enum TimestampError {
+    #[error(
+        "block timestamp {timestamp} is too far in the future, maximum allowed is {max_timestamp}"
+    )]
+    TooFarInFuture { timestamp: u64, max_timestamp: u64 },
+}
+
+/// Inflates a raw gas cost by `safety_margin_percent` (e.g. `10` adds 10% on top of `gas_cost`).
+fn apply_safety_margin(gas_cost: u64, safety_margin_percent: u64) -> U256 {
+    U256::from(gas_cost) * U256::from(100 + safety_margin_percent) / U256::from(100)
+}
+
+/// Pure cost-model core of [`IncompleteBlock::estimate_gas_limits`], taking each operation's
+/// chunk count directly so it can be exercised without constructing executed operations.
+fn estimate_gas_for_chunk_counts(
+    chunk_counts: impl IntoIterator<Item = usize>,
+    safety_margin_percent: u64,
+) -> (U256, U256) {
+    let (commit_gas, verify_gas) =
+        chunk_counts
+            .into_iter()
+            .fold((0u64, 0u64), |(commit_acc, verify_acc), chunks| {
+                let chunks = chunks as u64;
+                let commit_cost = COMMIT_BASE_GAS_PER_OP + chunks * COMMIT_GAS_PER_CHUNK;
+                let verify_cost = VERIFY_BASE_GAS_PER_OP + chunks * VERIFY_GAS_PER_CHUNK;
+                (commit_acc + commit_cost, verify_acc + verify_cost)
+            });
+
+    (
+        apply_safety_margin(commit_gas, safety_margin_percent),
+        apply_safety_margin(verify_gas, safety_margin_percent),
+    )
+}
+
+/// One greedily-packed group of operations: how many of the input operations it contains, the
+/// smallest supported block size that fits them, and how many of them are priority operations.
+#[derive(Debug, PartialEq, Eq)]
+struct PackedGroup {
+    tx_count: usize,
+    block_chunks_size: usize,
+    priority_ops: u64,
+}
+
+/// Pure bin-packing core of [`IncompleteBlock::pack_into_blocks`], operating on each operation's
+/// chunk count and whether it's a priority operation, decoupled from the concrete transaction
+/// type so the algorithm can be exercised directly in tests.
+///
+/// Greedily accumulates `ops` in order, sealing a group as soon as the next operation would no
+/// longer fit in the largest supported block size, then starting a new group for it. Returns one
+/// group per produced block, in order; returns an empty `Vec` for empty input.
+fn pack_chunk_groups(
+    ops: &[(usize, bool)],
+    available_block_chunks_sizes: &[usize],
+) -> Result<Vec<PackedGroup>, BlockPackingError> {
+    let max_block_size = available_block_chunks_sizes
+        .iter()
+        .copied()
+        .max()
+        .expect("available_block_chunks_sizes must not be empty");
+
+    let mut groups = Vec::new();
+    let mut pending_count = 0usize;
+    let mut pending_chunks = 0usize;
+    let mut pending_priority_ops = 0u64;
+
+    for &(op_chunks, is_priority_op) in ops {
+        if op_chunks > max_block_size {
+            return Err(BlockPackingError::OperationTooLarge {
+                op_chunks,
+                max_block_size,
+            });
+        }
+
+        if pending_count > 0 && pending_chunks + op_chunks > max_block_size {
+            groups.push(PackedGroup {
+                tx_count: pending_count,
+                block_chunks_size: smallest_block_size_for_chunks(
+                    pending_chunks,
+                    available_block_chunks_sizes,
+                ),
+                priority_ops: pending_priority_ops,
+            });
+            pending_count = 0;
+            pending_chunks = 0;
+            pending_priority_ops = 0;
+        }
+
+        if is_priority_op {
+            pending_priority_ops += 1;
+        }
+        pending_chunks += op_chunks;
+        pending_count += 1;
+    }
+
+    if pending_count > 0 {
+        groups.push(PackedGroup {
+            tx_count: pending_count,
+            block_chunks_size: smallest_block_size_for_chunks(
+                pending_chunks,
+                available_block_chunks_sizes,
+            ),
+            priority_ops: pending_priority_ops,
+        });
+    }
+
+    Ok(groups)
+}
+
 /// Gets smallest block size given the list of supported chunk sizes.
 fn smallest_block_size_for_chunks(chunks_used: usize, available_block_sizes: &[usize]) -> usize {
     for &block_size in available_block_sizes {
@@ -128,3 +563,271 @@ fn smallest_block_size_for_chunks(chunks_used: usize, available_block_sizes: &[u
         available_block_sizes.last().unwrap()
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AVAILABLE_SIZES: &[usize] = &[10, 50, 100];
+
+    #[test]
+    fn pack_chunk_groups_empty_input() {
+        let groups = pack_chunk_groups(&[], AVAILABLE_SIZES).unwrap();
+        assert!(groups.is_empty());
+    }
+
+    #[test]
+    fn pack_chunk_groups_exact_size_fits_one_block() {
+        // Chunks add up to exactly the largest available size: should stay in a single block.
+        let ops = [(40, false), (60, false)];
+        let groups = pack_chunk_groups(&ops, AVAILABLE_SIZES).unwrap();
+        assert_eq!(
+            groups,
+            vec![PackedGroup {
+                tx_count: 2,
+                block_chunks_size: 100,
+                priority_ops: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn pack_chunk_groups_splits_across_multiple_blocks() {
+        // Each op fits in a block on its own, but all three together overflow the largest size.
+        let ops = [(60, false), (60, false), (60, false)];
+        let groups = pack_chunk_groups(&ops, AVAILABLE_SIZES).unwrap();
+        assert_eq!(
+            groups,
+            vec![
+                PackedGroup {
+                    tx_count: 1,
+                    block_chunks_size: 100,
+                    priority_ops: 0,
+                },
+                PackedGroup {
+                    tx_count: 1,
+                    block_chunks_size: 100,
+                    priority_ops: 0,
+                },
+                PackedGroup {
+                    tx_count: 1,
+                    block_chunks_size: 100,
+                    priority_ops: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pack_chunk_groups_chooses_smallest_fitting_size() {
+        let ops = [(5, false), (3, false)];
+        let groups = pack_chunk_groups(&ops, AVAILABLE_SIZES).unwrap();
+        assert_eq!(
+            groups,
+            vec![PackedGroup {
+                tx_count: 2,
+                block_chunks_size: 10,
+                priority_ops: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn pack_chunk_groups_counts_priority_ops_per_group() {
+        let ops = [(60, true), (60, false), (60, true), (10, true)];
+        let groups = pack_chunk_groups(&ops, AVAILABLE_SIZES).unwrap();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].priority_ops, 1);
+        assert_eq!(groups[1].priority_ops, 0);
+        assert_eq!(groups[2].priority_ops, 2);
+    }
+
+    #[test]
+    fn pack_chunk_groups_errors_on_oversized_operation() {
+        let ops = [(10, false), (101, false)];
+        let err = pack_chunk_groups(&ops, AVAILABLE_SIZES).unwrap_err();
+        assert!(matches!(
+            err,
+            BlockPackingError::OperationTooLarge {
+                op_chunks: 101,
+                max_block_size: 100,
+            }
+        ));
+    }
+
+    #[test]
+    fn priority_ops_cursor_chains_across_blocks() {
+        // Mirrors pack_into_blocks's block-assembly loop to verify the cursor chaining invariant
+        // without needing ExecutedOperations fixtures: each block's processed_priority_ops.1
+        // must equal the next block's processed_priority_ops.0.
+        let groups =
+            pack_chunk_groups(&[(60, true), (60, false), (60, true)], AVAILABLE_SIZES).unwrap();
+
+        let mut cursor = 5u64;
+        let mut ranges = Vec::new();
+        for group in &groups {
+            let next_cursor = cursor + group.priority_ops;
+            ranges.push((cursor, next_cursor));
+            cursor = next_cursor;
+        }
+
+        for window in ranges.windows(2) {
+            assert_eq!(window[0].1, window[1].0);
+        }
+        assert_eq!(ranges.first().unwrap().0, 5);
+        assert_eq!(ranges.last().unwrap().1, 5 + 2);
+    }
+
+    #[test]
+    fn pack_into_blocks_returns_empty_vec_for_empty_input() {
+        let blocks = IncompleteBlock::pack_into_blocks(
+            0,
+            0,
+            Vec::new(),
+            (0, 0),
+            AVAILABLE_SIZES,
+            GasLimits::Fixed(U256::zero(), U256::zero()),
+            0,
+        )
+        .unwrap();
+        assert!(blocks.is_empty());
+    }
+
+    #[test]
+    fn estimate_gas_for_chunk_counts_applies_safety_margin() {
+        let (commit_gas, verify_gas) = estimate_gas_for_chunk_counts([10, 20], 0);
+        let expected_commit = COMMIT_BASE_GAS_PER_OP * 2 + (10 + 20) * COMMIT_GAS_PER_CHUNK;
+        let expected_verify = VERIFY_BASE_GAS_PER_OP * 2 + (10 + 20) * VERIFY_GAS_PER_CHUNK;
+        assert_eq!(commit_gas, U256::from(expected_commit));
+        assert_eq!(verify_gas, U256::from(expected_verify));
+
+        let (commit_gas_margin, verify_gas_margin) = estimate_gas_for_chunk_counts([10, 20], 10);
+        assert_eq!(
+            commit_gas_margin,
+            U256::from(expected_commit) * U256::from(110) / U256::from(100)
+        );
+        assert_eq!(
+            verify_gas_margin,
+            U256::from(expected_verify) * U256::from(110) / U256::from(100)
+        );
+    }
+
+    #[test]
+    fn estimate_gas_for_chunk_counts_empty_is_zero() {
+        let (commit_gas, verify_gas) = estimate_gas_for_chunk_counts(std::iter::empty(), 50);
+        assert_eq!(commit_gas, U256::zero());
+        assert_eq!(verify_gas, U256::zero());
+    }
+
+    #[test]
+    fn estimate_gas_limits_of_block_without_operations_is_zero() {
+        // A block with no operations (e.g. the `filter_map` in `estimate_gas_limits` would have
+        // nothing to sum over) must estimate to zero gas, same as `chunks_used` sums to zero.
+        let block =
+            IncompleteBlock::new(0, 0, Vec::new(), (0, 0), 10, U256::zero(), U256::zero(), 0);
+        assert_eq!(block.estimate_gas_limits(25), (U256::zero(), U256::zero()));
+    }
+
+    #[test]
+    fn new_from_available_block_sizes_with_fixed_gas_limits() {
+        let block = IncompleteBlock::new_from_available_block_sizes(
+            0,
+            0,
+            Vec::new(),
+            (0, 0),
+            AVAILABLE_SIZES,
+            U256::from(111),
+            U256::from(222),
+            0,
+        );
+        assert_eq!(block.commit_gas_limit, U256::from(111));
+        assert_eq!(block.verify_gas_limit, U256::from(222));
+    }
+
+    #[test]
+    fn new_from_available_block_sizes_with_estimated_gas_limits() {
+        let block = IncompleteBlock::new_from_available_block_sizes_with_estimated_gas(
+            0,
+            0,
+            Vec::new(),
+            (0, 0),
+            AVAILABLE_SIZES,
+            20,
+            0,
+        );
+        // No transactions means no gas cost to estimate, regardless of the safety margin.
+        assert_eq!(block.commit_gas_limit, U256::zero());
+        assert_eq!(block.verify_gas_limit, U256::zero());
+    }
+
+    fn test_block() -> IncompleteBlock {
+        IncompleteBlock::new(
+            1,
+            7,
+            Vec::new(),
+            (0, 3),
+            10,
+            U256::from(100),
+            U256::from(200),
+            1_000,
+        )
+    }
+
+    #[test]
+    fn snapshot_revert_to_restores_all_fields() {
+        let mut block = test_block();
+        let snapshot = block.snapshot();
+
+        // Simulate state that would result from speculatively appending more operations:
+        // the block number, priority-op cursor and (re-estimated) gas limits all move forward.
+        block.block_number += 1;
+        block.processed_priority_ops = (3, 9);
+        block.commit_gas_limit = U256::from(500);
+        block.verify_gas_limit = U256::from(900);
+
+        block.revert_to(snapshot, AVAILABLE_SIZES).unwrap();
+
+        assert_eq!(block.block_number, 1);
+        assert_eq!(block.processed_priority_ops, (0, 3));
+        assert_eq!(block.commit_gas_limit, U256::from(100));
+        assert_eq!(block.verify_gas_limit, U256::from(200));
+        assert!(block.block_transactions.is_empty());
+        assert_eq!(block.block_chunks_size, AVAILABLE_SIZES[0]);
+    }
+
+    #[test]
+    fn snapshot_taken_before_any_operations_is_a_no_op_revert() {
+        let mut block = test_block();
+        let snapshot = block.snapshot();
+        block.revert_to(snapshot, AVAILABLE_SIZES).unwrap();
+        assert_eq!(block.block_number, 1);
+        assert_eq!(block.processed_priority_ops, (0, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot revert to a snapshot taken after the current state")]
+    fn revert_to_panics_on_snapshot_from_the_future() {
+        let mut block = test_block();
+        // Constructed directly (rather than via `snapshot()`) to simulate a snapshot that
+        // recorded more transactions than the block currently holds.
+        let bogus_snapshot = BlockSnapshot {
+            block_number: block.block_number,
+            processed_priority_ops: block.processed_priority_ops,
+            block_transactions_len: block.block_transactions.len() + 1,
+            commit_gas_limit: block.commit_gas_limit,
+            verify_gas_limit: block.verify_gas_limit,
+        };
+        block.revert_to(bogus_snapshot, AVAILABLE_SIZES).unwrap();
+    }
+
+    #[test]
+    fn revert_to_errors_when_no_available_size_fits() {
+        let mut block = test_block();
+        let snapshot = block.snapshot();
+        let err = block.revert_to(snapshot, &[]).unwrap_err();
+        assert!(matches!(
+            err,
+            RevertError::NoFittingBlockSize { chunks_used: 0 }
+        ));
+    }
+}